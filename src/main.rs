@@ -1,7 +1,14 @@
 use std::net::SocketAddr;
 
-use axum::{extract::Path, response::IntoResponse, routing::get, Json, Router};
-use chrono::DateTime;
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Offset};
+use chrono_tz::Tz;
 use hyper::Method;
 use serde::{Deserialize, Serialize};
 use tower_http::cors::{Any, CorsLayer};
@@ -11,10 +18,13 @@ struct Date {
     date: String,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct Response {
-    unix: i64,
+    unix_seconds: i64,
+    unix_millis: i64,
+    unix_nanos: i64,
     utc: String,
+    offset: i32,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -22,68 +32,232 @@ struct Error {
     error: String,
 }
 
-// handle api requests without a date string and return the current unix and utc date
-async fn now_handler() -> impl IntoResponse {
-    let date = chrono::Utc::now();
-    let unix = date.timestamp_millis();
-    let utc = date
-        .with_timezone(&chrono::FixedOffset::east_opt(0).unwrap())
-        .format("%a, %d %b %Y %H:%M:%S GMT")
-        .to_string();
-
-    Json(Response { unix, utc })
-}
-
-fn parse_date_or_timestamp(date: String) -> anyhow::Result<Response> {
-    match date.parse::<i64>() {
-        Ok(secs) => {
-            let date = DateTime::<chrono::Utc>::from_timestamp(secs / 1000, 0).unwrap();
-            let utc = date
-                .with_timezone(&chrono::FixedOffset::east_opt(0).unwrap())
-                .format("%a, %d %b %Y %H:%M:%S GMT")
-                .to_string();
-            Ok(Response { unix: secs, utc })
+// everything that can go wrong while resolving a request into a rendered `Response`
+#[derive(Debug)]
+enum ParseError {
+    UnrecognizedFormat(String),
+    OutOfRange,
+    InvalidTimezone(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnrecognizedFormat(detail) => write!(f, "Unrecognized format: {}", detail),
+            ParseError::OutOfRange => write!(f, "Timestamp out of range"),
+            ParseError::InvalidTimezone(name) => write!(f, "Unknown timezone: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl IntoResponse for ParseError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            ParseError::UnrecognizedFormat(_) => StatusCode::BAD_REQUEST,
+            ParseError::OutOfRange => StatusCode::UNPROCESSABLE_ENTITY,
+            ParseError::InvalidTimezone(_) => StatusCode::NOT_FOUND,
+        };
+
+        (
+            status,
+            Json(Error {
+                error: self.to_string(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct QueryParams {
+    tz: Option<String>,
+    unit: Option<String>,
+    format: Option<String>,
+    pattern: Option<String>,
+}
+
+// rendering of the `utc` field, selected via the `format` query parameter
+enum OutputFormat {
+    Rfc3339,
+    Rfc2822,
+    IsoWeek,
+    Http,
+    Custom(String),
+}
+
+// resolve the `format`/`pattern` query parameters, defaulting to the legacy GMT form
+fn resolve_format(
+    format: &Option<String>,
+    pattern: &Option<String>,
+) -> Result<OutputFormat, ParseError> {
+    match format.as_deref() {
+        None | Some("http") => Ok(OutputFormat::Http),
+        Some("rfc3339") => Ok(OutputFormat::Rfc3339),
+        Some("rfc2822") => Ok(OutputFormat::Rfc2822),
+        Some("iso_week") => Ok(OutputFormat::IsoWeek),
+        Some("custom") => match pattern {
+            Some(pattern) => Ok(OutputFormat::Custom(pattern.clone())),
+            None => Err(ParseError::UnrecognizedFormat(
+                "missing pattern for custom format".to_string(),
+            )),
+        },
+        Some(other) => Err(ParseError::UnrecognizedFormat(other.to_string())),
+    }
+}
+
+// render `date` with a custom strftime pattern, rejecting invalid patterns instead of
+// letting chrono panic partway through formatting
+fn render_pattern(date: DateTime<Tz>, pattern: &str) -> Result<String, ParseError> {
+    let items: Vec<_> = chrono::format::StrftimeItems::new(pattern).collect();
+    if items.iter().any(|item| matches!(item, chrono::format::Item::Error)) {
+        return Err(ParseError::UnrecognizedFormat(pattern.to_string()));
+    }
+    Ok(date.format_with_items(items.into_iter()).to_string())
+}
+
+// epoch precision for a numeric path segment
+#[derive(Clone, Copy)]
+enum Unit {
+    Seconds,
+    Millis,
+    Nanos,
+}
+
+impl Unit {
+    fn parse(raw: &str) -> Result<Self, ParseError> {
+        match raw {
+            "s" => Ok(Unit::Seconds),
+            "ms" => Ok(Unit::Millis),
+            "ns" => Ok(Unit::Nanos),
+            other => Err(ParseError::UnrecognizedFormat(other.to_string())),
         }
-        Err(_) => {
-            let date = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
-                .or_else(|_| chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%dT%H:%M:%S"))
-                .or_else(|_| chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%dT%H:%M:%S%.f"))
-                .or_else(|_| chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%dT%H:%M:%S%.fZ"))
-                .or_else(|_| chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%dT%H:%M:%S%.f%:z"))
-                .or_else(|_| chrono::NaiveDate::parse_from_str(&date, "%d %B %Y, %Z"))
-                .or_else(|_| chrono::NaiveDate::parse_from_str(&date, "%a %b %d %Y %H:%M:%S GMT%z"))
-                .or_else(|_| chrono::NaiveDate::parse_from_str(&date, "%a %b %d %Y %H:%M:%S %z"))
-                .or_else(|_| {
-                    chrono::NaiveDate::parse_from_str(&date, "%a %b %d %Y %H:%M:%S GMT%:z (%Z)")
-                })
-                .or_else(|_| {
-                    chrono::NaiveDate::parse_from_str(&date, "%a %b %d %Y %H:%M:%S %:z (%Z)")
-                });
-            match date {
-                Ok(date) => {
-                    let date = date.and_hms_opt(0, 0, 0).unwrap();
-                    let secs = date.and_utc().timestamp_millis();
-                    let utc = date
-                        .and_utc()
-                        .with_timezone(&chrono::FixedOffset::east_opt(0).unwrap())
-                        .format("%a, %d %b %Y %H:%M:%S GMT")
-                        .to_string();
-                    Ok(Response { unix: secs, utc })
-                }
-                Err(_) => Err(anyhow::Error::msg("Invalid Date")),
-            }
+    }
+
+    // guess the precision from how many digits were supplied:
+    // seconds are <= 11 digits, millis ~13, nanos 16+
+    fn autodetect(digits: &str) -> Self {
+        match digits.trim_start_matches(['+', '-']).len() {
+            0..=11 => Unit::Seconds,
+            12..=14 => Unit::Millis,
+            _ => Unit::Nanos,
         }
     }
 }
 
-// handle api requests with a date string and return the unix and utc date
-async fn date_handler(Path(date): Path<String>) -> impl IntoResponse {
-    match parse_date_or_timestamp(date) {
-        Ok(res) => Ok(Json(res)),
-        Err(_) => Err(Json(Error {
-            error: "Invalid Date".to_string(),
-        })),
+// resolve the `tz` query parameter into an IANA timezone, defaulting to UTC
+fn resolve_tz(tz: &Option<String>) -> Result<Tz, ParseError> {
+    match tz {
+        Some(name) => name
+            .parse::<Tz>()
+            .map_err(|_| ParseError::InvalidTimezone(name.clone())),
+        None => Ok(chrono_tz::UTC),
+    }
+}
+
+// resolve the `unit` query parameter, defaulting to autodetection from `raw`
+fn resolve_unit(unit: &Option<String>, raw: &str) -> Result<Unit, ParseError> {
+    match unit {
+        Some(unit) => Unit::parse(unit),
+        None => Ok(Unit::autodetect(raw)),
+    }
+}
+
+fn parse_epoch_value(n: i64, unit: Unit) -> Option<DateTime<chrono::Utc>> {
+    match unit {
+        Unit::Seconds => DateTime::from_timestamp(n, 0),
+        Unit::Millis => DateTime::from_timestamp_millis(n),
+        Unit::Nanos => {
+            let secs = n.div_euclid(1_000_000_000);
+            let nsecs = n.rem_euclid(1_000_000_000) as u32;
+            DateTime::from_timestamp(secs, nsecs)
+        }
+    }
+}
+
+fn render(
+    date: DateTime<chrono::Utc>,
+    tz: Tz,
+    format: OutputFormat,
+) -> Result<Response, ParseError> {
+    let local = date.with_timezone(&tz);
+    let utc = match format {
+        OutputFormat::Http => local.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        OutputFormat::Rfc3339 => local.to_rfc3339(),
+        OutputFormat::Rfc2822 => local.to_rfc2822(),
+        OutputFormat::IsoWeek => local.format("%G-W%V-%u").to_string(),
+        OutputFormat::Custom(pattern) => render_pattern(local, &pattern)?,
+    };
+
+    Ok(Response {
+        unix_seconds: date.timestamp(),
+        unix_millis: date.timestamp_millis(),
+        unix_nanos: date.timestamp_nanos_opt().ok_or(ParseError::OutOfRange)?,
+        utc,
+        offset: local.offset().fix().local_minus_utc(),
+    })
+}
+
+// handle api requests without a date string and return the current unix and utc date
+async fn now_handler(Query(query): Query<QueryParams>) -> Result<Json<Response>, ParseError> {
+    let tz = resolve_tz(&query.tz)?;
+    let format = resolve_format(&query.format, &query.pattern)?;
+
+    Ok(Json(render(chrono::Utc::now(), tz, format)?))
+}
+
+// parse a date string into a full instant, preserving time-of-day and sub-second
+// precision when present; only defaults to midnight when no time was supplied
+fn parse_date(date: &str) -> Result<DateTime<chrono::Utc>, chrono::ParseError> {
+    DateTime::parse_from_rfc3339(date)
+        .or_else(|_| DateTime::parse_from_rfc2822(date))
+        .or_else(|_| DateTime::parse_from_str(date, "%a %b %d %Y %H:%M:%S GMT%z"))
+        .or_else(|_| DateTime::parse_from_str(date, "%a %b %d %Y %H:%M:%S %z"))
+        .or_else(|_| DateTime::parse_from_str(date, "%a %b %d %Y %H:%M:%S GMT%:z (%Z)"))
+        .or_else(|_| DateTime::parse_from_str(date, "%a %b %d %Y %H:%M:%S %:z (%Z)"))
+        .map(|date| date.with_timezone(&chrono::Utc))
+        .or_else(|_| {
+            chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S")
+                .or_else(|_| chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f"))
+                .map(|date| date.and_utc())
+        })
+        .or_else(|e| {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .or_else(|_| chrono::NaiveDate::parse_from_str(date, "%d %B %Y, %Z"))
+                .and_then(|date| date.and_hms_opt(0, 0, 0).ok_or(e))
+                .map(|date| date.and_utc())
+        })
+}
+
+// an optional sign followed by one or more digits: a numeric path segment, even one
+// too large to fit an `i64`, should be classified as out-of-range rather than falling
+// through to date parsing (which would only ever reject it as unrecognized)
+fn is_all_digits(date: &str) -> bool {
+    let digits = date.strip_prefix(['+', '-']).unwrap_or(date);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn resolve_instant(date: &str, unit: Unit) -> Result<DateTime<chrono::Utc>, ParseError> {
+    if is_all_digits(date) {
+        let n = date.parse::<i64>().map_err(|_| ParseError::OutOfRange)?;
+        return parse_epoch_value(n, unit).ok_or(ParseError::OutOfRange);
     }
+
+    parse_date(date).map_err(|_| ParseError::UnrecognizedFormat(date.to_string()))
+}
+
+// handle api requests with a date string and return the unix and utc date
+async fn date_handler(
+    Path(date): Path<String>,
+    Query(query): Query<QueryParams>,
+) -> Result<Json<Response>, ParseError> {
+    let tz = resolve_tz(&query.tz)?;
+    let unit = resolve_unit(&query.unit, &date)?;
+    let format = resolve_format(&query.format, &query.pattern)?;
+    let instant = resolve_instant(&date, unit)?;
+
+    Ok(Json(render(instant, tz, format)?))
 }
 
 #[tokio::main]
@@ -114,3 +288,21 @@ async fn main() {
         .await
         .expect("server failed");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_instant_reports_out_of_range_instead_of_panicking() {
+        let err = resolve_instant(&i64::MAX.to_string(), Unit::Seconds).unwrap_err();
+        assert!(matches!(err, ParseError::OutOfRange));
+    }
+
+    #[test]
+    fn render_reports_invalid_custom_pattern_instead_of_panicking() {
+        let err = render(chrono::Utc::now(), chrono_tz::UTC, OutputFormat::Custom("%".to_string()))
+            .unwrap_err();
+        assert!(matches!(err, ParseError::UnrecognizedFormat(_)));
+    }
+}